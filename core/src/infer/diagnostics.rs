@@ -0,0 +1,74 @@
+use crate::internal::*;
+use super::InferenceFact;
+use std::fmt;
+
+/// A single incompatible edge discovered during inference.
+///
+/// Instead of short-circuiting on the first unrecoverable conflict, each failed
+/// shape/dtype equality is captured as one of these so tooling can dump every
+/// broken edge in one pass. The `inputs`/`outputs` are the actual facts the
+/// failing node was reconciling, not a placeholder pair.
+#[derive(Clone, Debug)]
+pub struct InferenceDiagnostic {
+    /// The node whose inference raised the conflict.
+    pub node: String,
+    /// The outlets whose facts the node relates.
+    pub outlets: TVec<OutletId>,
+    /// The input facts presented to the failing node.
+    pub inputs: TVec<InferenceFact>,
+    /// The output facts presented to the failing node.
+    pub outputs: TVec<InferenceFact>,
+    /// The error raised at the failing equality site.
+    pub cause: String,
+}
+
+impl fmt::Display for InferenceDiagnostic {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}: {} (inputs: {:?}, outputs: {:?})", self.node, self.cause, self.inputs, self.outputs)
+    }
+}
+
+/// Collector passed to `InferenceOp::infer_with_diagnostics`.
+///
+/// When supplied, a node whose inference fails pushes an [`InferenceDiagnostic`]
+/// and the call returns `Ok(None)` instead of an error, so a caller can go on
+/// with the remaining nodes and read the full list at the end. `infer`,
+/// `infer_facts` and the `Solver` do not take a collector, and the analyser
+/// does not use one.
+#[derive(Clone, Debug, Default)]
+pub struct InferenceDiagnostics {
+    diagnostics: Vec<InferenceDiagnostic>,
+}
+
+impl InferenceDiagnostics {
+    pub fn new() -> InferenceDiagnostics {
+        InferenceDiagnostics::default()
+    }
+
+    /// Record one incompatible edge.
+    pub fn push(&mut self, diagnostic: InferenceDiagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// All diagnostics collected so far.
+    pub fn diagnostics(&self) -> &[InferenceDiagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.diagnostics.len()
+    }
+}
+
+impl fmt::Display for InferenceDiagnostics {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for d in &self.diagnostics {
+            writeln!(fmt, "{}", d)?;
+        }
+        Ok(())
+    }
+}
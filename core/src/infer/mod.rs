@@ -3,10 +3,16 @@ use downcast_rs::Downcast;
 use crate::internal::*;
 
 pub mod ops;
+mod diagnostics;
 mod fact;
 mod model;
+mod obligation;
+mod unify;
 
+pub use self::diagnostics::{InferenceDiagnostic, InferenceDiagnostics};
 pub use self::fact::InferenceFact;
+pub use self::obligation::{Obligation, ObligationWorklist};
+pub use self::unify::{unify_values, UnificationTable, UnifyKey, UnifySolver, UnifyValue};
 
 /// A model with partially types and shapes, as produced by parsing ONNX or
 /// Tensorflow graphs.
@@ -68,6 +74,59 @@ pub trait InferenceOp:
         return Ok((infered_inputs, infered_outputs, observed));
     }
 
+    /// Run inference while routing conflicts into a diagnostics sink.
+    ///
+    /// On success the refined facts are returned as `Some`. On an unrecoverable
+    /// conflict the offending facts are pushed into the `diagnostics` collector
+    /// (when one is supplied) and the method returns `None`: the node has *not*
+    /// converged, and the caller must not mistake it for a resolved node. This
+    /// lets the analyser record the broken edge and move on to the remaining
+    /// nodes instead of aborting the whole pass. With no collector, the conflict
+    /// propagates as an error exactly like `infer`.
+    fn infer_with_diagnostics(
+        &mut self,
+        inputs: TVec<&InferenceFact>,
+        outputs: TVec<&InferenceFact>,
+        observed: TVec<&InferenceFact>,
+        node: &str,
+        outlets: &[OutletId],
+        diagnostics: Option<&mut InferenceDiagnostics>,
+    ) -> TractResult<Option<(TVec<InferenceFact>, TVec<InferenceFact>, TVec<InferenceFact>)>> {
+        let offending: (TVec<InferenceFact>, TVec<InferenceFact>) = (
+            inputs.iter().map(|i| (*i).clone()).collect(),
+            outputs.iter().map(|o| (*o).clone()).collect(),
+        );
+        match self.infer(inputs, outputs, observed) {
+            Ok(refined) => Ok(Some(refined)),
+            Err(e) => match diagnostics {
+                Some(diagnostics) => {
+                    // capture the actual facts the node was reconciling, so the
+                    // sink reports the real offending edge rather than a stand-in.
+                    diagnostics.push(InferenceDiagnostic {
+                        node: node.to_string(),
+                        outlets: outlets.iter().cloned().collect(),
+                        inputs: offending.0,
+                        outputs: offending.1,
+                        cause: e.to_string(),
+                    });
+                    Ok(None)
+                }
+                None => Err(e),
+            },
+        }
+    }
+
+    /// Constraints the op could not resolve locally, to be retried when one of
+    /// their watched variables changes value.
+    ///
+    /// A caller can register these with an [`ObligationWorklist`] (directly or
+    /// through [`UnifySolver::defer`]) so that an op is only re-invoked when one
+    /// of the variables it watches gets resolved. The analyser does not call
+    /// this method. The default is no pending obligations.
+    fn obligations(&self) -> TVec<Obligation> {
+        tvec![]
+    }
+
     /// Allow an op to specify a supplementary list of outlets facts that
     /// will trigger inference again.
     fn observe_outlets(
@@ -164,3 +223,75 @@ impl AsMut<dyn Op> for Box<dyn InferenceOp> {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An op whose inference always conflicts, to exercise the diagnostics sink.
+    #[derive(Debug, Clone)]
+    struct AlwaysConflicts;
+
+    impl Op for AlwaysConflicts {
+        fn name(&self) -> Cow<str> {
+            "AlwaysConflicts".into()
+        }
+        op_as_typed_op!();
+        not_a_typed_op!();
+    }
+
+    impl StatelessOp for AlwaysConflicts {
+        fn eval(&self, _inputs: TVec<Arc<Tensor>>) -> TractResult<TVec<Arc<Tensor>>> {
+            bail!("not evaluable")
+        }
+    }
+
+    impl InferenceRulesOp for AlwaysConflicts {
+        fn rules<'r, 'p: 'r, 's: 'r>(
+            &'s self,
+            s: &mut Solver<'r>,
+            inputs: &'p [TensorProxy],
+            _outputs: &'p [TensorProxy],
+        ) -> InferenceResult {
+            s.equals(&inputs[0].datum_type, f32::datum_type())?;
+            s.equals(&inputs[0].datum_type, i64::datum_type())
+        }
+
+        inference_op_as_op!();
+    }
+
+    #[test]
+    fn collects_conflict_instead_of_bailing() {
+        let mut op = AlwaysConflicts;
+        let input = InferenceFact::dt_shape(f32::datum_type(), tvec![1.to_dim()]);
+        let output = InferenceFact::default();
+        let mut diagnostics = InferenceDiagnostics::new();
+
+        let result = op.infer_with_diagnostics(
+            tvec![&input],
+            tvec![&output],
+            tvec![],
+            "node0",
+            &[OutletId::new(0, 0)],
+            Some(&mut diagnostics),
+        );
+
+        // the node is reported as unresolved (not a false success)...
+        assert!(matches!(result, Ok(None)));
+        // ...and the real offending facts land in the sink.
+        assert_eq!(diagnostics.len(), 1);
+        let d = &diagnostics.diagnostics()[0];
+        assert_eq!(d.node, "node0");
+        assert_eq!(d.inputs[0], input);
+    }
+
+    #[test]
+    fn bails_when_no_sink_present() {
+        let mut op = AlwaysConflicts;
+        let input = InferenceFact::dt_shape(f32::datum_type(), tvec![1.to_dim()]);
+        let output = InferenceFact::default();
+        let result =
+            op.infer_with_diagnostics(tvec![&input], tvec![&output], tvec![], "node0", &[], None);
+        assert!(result.is_err());
+    }
+}
+
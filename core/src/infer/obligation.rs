@@ -0,0 +1,166 @@
+use crate::internal::*;
+use super::UnifyKey;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+/// A constraint an op could not resolve locally, parked until one of the
+/// variables it watches gets a value.
+///
+/// Rather than re-running an op's rules on every pass, the analyser resolves
+/// what it can now and keeps what it can't as an `Obligation` keyed by the
+/// unresolved variables it depends on. The worklist retries it only when one of
+/// `watched` is pinned.
+#[derive(Clone)]
+pub struct Obligation {
+    /// The unresolved variables whose resolution should wake this obligation.
+    pub watched: TVec<UnifyKey>,
+    /// The outlets the parked constraint relates.
+    pub outlets: TVec<OutletId>,
+    /// Human-readable description of what is still blocked, e.g.
+    /// "dimension 2 of node conv depends on node reshape".
+    pub cause: String,
+}
+
+impl fmt::Debug for Obligation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "unresolved: {} (watching {:?})", self.cause, self.watched)
+    }
+}
+
+impl fmt::Display for Obligation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "unresolved: {}", self.cause)
+    }
+}
+
+/// Worklist of parked obligations, keyed by the variables they watch.
+///
+/// An op's [`Obligation`]s are registered once with [`register`](Self::register);
+/// only when one of their watched variables is pinned — signalled via
+/// [`resolved`](Self::resolved) — do the affected obligations become ready to
+/// retry. This keeps re-invocation targeted: an op is woken precisely when a
+/// value it waits on changes, never on an unrelated update.
+#[derive(Default)]
+pub struct ObligationWorklist {
+    /// Parked obligations, addressed by a stable slot.
+    parked: HashMap<usize, Obligation>,
+    /// For each watched variable, the slots blocked on it.
+    watchers: HashMap<UnifyKey, HashSet<usize>>,
+    /// The variables each slot watches, so a popped slot can be unindexed
+    /// without scanning every watcher set.
+    slot_keys: HashMap<usize, TVec<UnifyKey>>,
+    /// Slots woken and waiting to be drained.
+    ready: VecDeque<usize>,
+    next_slot: usize,
+}
+
+impl ObligationWorklist {
+    pub fn new() -> ObligationWorklist {
+        ObligationWorklist::default()
+    }
+
+    /// Park an obligation, indexing it under each variable it watches.
+    pub fn register(&mut self, obligation: Obligation) {
+        let slot = self.next_slot;
+        self.next_slot += 1;
+        for key in &obligation.watched {
+            self.watchers.entry(*key).or_default().insert(slot);
+        }
+        self.slot_keys.insert(slot, obligation.watched.clone());
+        self.parked.insert(slot, obligation);
+    }
+
+    /// Signal that `key` just got a value; move every obligation watching it to
+    /// the ready queue.
+    pub fn resolved(&mut self, key: UnifyKey) {
+        if let Some(slots) = self.watchers.remove(&key) {
+            for slot in slots {
+                // a slot watching several now-resolved keys is only queued once.
+                if self.parked.contains_key(&slot) && !self.ready.contains(&slot) {
+                    self.ready.push_back(slot);
+                }
+            }
+        }
+    }
+
+    /// Pop the next obligation ready to be retried, if any.
+    pub fn next_ready(&mut self) -> Option<Obligation> {
+        while let Some(slot) = self.ready.pop_front() {
+            if let Some(obligation) = self.parked.remove(&slot) {
+                // unindex this slot from exactly the variables it watched,
+                // rather than scanning every watcher set.
+                if let Some(keys) = self.slot_keys.remove(&slot) {
+                    for key in keys {
+                        if let Some(set) = self.watchers.get_mut(&key) {
+                            set.remove(&slot);
+                        }
+                    }
+                }
+                return Some(obligation);
+            }
+        }
+        None
+    }
+
+    /// Obligations still parked once the worklist drains — the ones that never
+    /// got enough information to resolve. The diagnostics sink can report these
+    /// as "unresolved: ...".
+    pub fn pending(&self) -> impl Iterator<Item = &Obligation> {
+        self.parked.values()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parked.is_empty() && self.ready.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn obligation(watched: TVec<UnifyKey>) -> Obligation {
+        Obligation { watched, outlets: tvec![], cause: "test".to_string() }
+    }
+
+    #[test]
+    fn only_wakes_on_watched_variable() {
+        let mut table = crate::infer::UnificationTable::new();
+        let watched = table.new_key();
+        let other = table.new_key();
+        let mut worklist = ObligationWorklist::new();
+        worklist.register(obligation(tvec![watched]));
+
+        // an unrelated variable resolving wakes nothing.
+        worklist.resolved(other);
+        assert!(worklist.next_ready().is_none());
+
+        // the watched one does.
+        worklist.resolved(watched);
+        assert!(worklist.next_ready().is_some());
+        assert!(worklist.is_empty());
+    }
+
+    #[test]
+    fn obligation_watching_two_keys_wakes_once() {
+        let mut table = crate::infer::UnificationTable::new();
+        let a = table.new_key();
+        let b = table.new_key();
+        let mut worklist = ObligationWorklist::new();
+        worklist.register(obligation(tvec![a, b]));
+
+        worklist.resolved(a);
+        worklist.resolved(b);
+        assert!(worklist.next_ready().is_some());
+        assert!(worklist.next_ready().is_none());
+    }
+
+    #[test]
+    fn unresolved_obligations_stay_pending() {
+        let mut table = crate::infer::UnificationTable::new();
+        let watched = table.new_key();
+        let mut worklist = ObligationWorklist::new();
+        worklist.register(obligation(tvec![watched]));
+        // nothing resolved: the obligation is still pending for diagnostics.
+        assert_eq!(worklist.pending().count(), 1);
+    }
+}
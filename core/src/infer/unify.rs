@@ -0,0 +1,312 @@
+use crate::internal::*;
+use super::ObligationWorklist;
+use std::fmt;
+
+/// A variable key in the unification table.
+///
+/// Every unknown scalar quantity carried by an `InferenceFact` gets one key: the
+/// datum type, the rank, and one key per shape dimension. Keys live in a single
+/// union-find structure, so an equality is an in-place merge and saturation is
+/// near-linear instead of the quadratic rule-iteration fixpoint.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct UnifyKey(u32);
+
+impl UnifyKey {
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// The value stored for the representative of a union-find class.
+///
+/// A class is `Unknown` until a concrete fact pins it down. Merging two
+/// variables only joins their classes; it is pinning a class to a concrete value
+/// that moves it from `Unknown` to `Known`.
+#[derive(Clone, PartialEq)]
+pub enum UnifyValue {
+    Unknown,
+    Dt(DatumType),
+    Rank(usize),
+    Dim(TDim),
+}
+
+impl fmt::Debug for UnifyValue {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnifyValue::Unknown => write!(fmt, "?"),
+            UnifyValue::Dt(dt) => write!(fmt, "{:?}", dt),
+            UnifyValue::Rank(r) => write!(fmt, "rank={}", r),
+            UnifyValue::Dim(d) => write!(fmt, "{}", d),
+        }
+    }
+}
+
+impl UnifyValue {
+    fn is_unknown(&self) -> bool {
+        *self == UnifyValue::Unknown
+    }
+}
+
+/// Reconcile two values attached to classes that are being merged.
+///
+/// Returns the concrete value when exactly one side is `Unknown`, keeps it when
+/// both agree, and errors (carrying both offending values) when two known values
+/// disagree. Conflicts are detected here, at merge time, rather than after a
+/// global fixpoint.
+pub fn unify_values(a: &UnifyValue, b: &UnifyValue) -> TractResult<UnifyValue> {
+    match (a, b) {
+        (UnifyValue::Unknown, other) | (other, UnifyValue::Unknown) => Ok(other.clone()),
+        (UnifyValue::Dim(a), UnifyValue::Dim(b)) => unify_dims(a, b).map(UnifyValue::Dim),
+        (a, b) if a == b => Ok(a.clone()),
+        (a, b) => bail!("Unification conflict between {:?} and {:?}", a, b),
+    }
+}
+
+/// Unify two dimensions structurally.
+///
+/// Equal dimensions are trivially compatible. A symbol paired with a concrete
+/// integer keeps the concrete side (the symbol's value is now known). Two
+/// distinct concrete integers conflict, and so do two distinct symbols: we have
+/// no place to record the pending `S == P` equality here, so collapsing one onto
+/// the other would silently drop a constraint.
+fn unify_dims(a: &TDim, b: &TDim) -> TractResult<TDim> {
+    if a == b {
+        return Ok(a.clone());
+    }
+    match (a.as_const(), b.as_const()) {
+        (Some(_), None) => Ok(a.clone()),
+        (None, Some(_)) => Ok(b.clone()),
+        _ => bail!("Dimension conflict between {} and {}", a, b),
+    }
+}
+
+/// In-place union-find table over [`UnifyKey`]s.
+///
+/// Merges classes, pins classes to concrete values, and reads each fact back by
+/// probing its representative once saturation is done.
+#[derive(Clone, Default)]
+pub struct UnificationTable {
+    parent: Vec<UnifyKey>,
+    rank: Vec<u32>,
+    value: Vec<UnifyValue>,
+}
+
+impl UnificationTable {
+    pub fn new() -> UnificationTable {
+        UnificationTable::default()
+    }
+
+    /// Allocate a fresh variable, initially `Unknown`.
+    pub fn new_key(&mut self) -> UnifyKey {
+        let key = UnifyKey(self.parent.len() as u32);
+        self.parent.push(key);
+        self.rank.push(0);
+        self.value.push(UnifyValue::Unknown);
+        key
+    }
+
+    /// Representative of `key`'s class, with path compression.
+    pub fn find(&mut self, key: UnifyKey) -> UnifyKey {
+        let parent = self.parent[key.index()];
+        if parent == key {
+            key
+        } else {
+            let root = self.find(parent);
+            self.parent[key.index()] = root;
+            root
+        }
+    }
+
+    /// Merge the classes of `a` and `b`, reconciling their stored values.
+    ///
+    /// Returns the class value after the merge so callers can tell whether the
+    /// merge pinned a previously-unknown variable.
+    pub fn union(&mut self, a: UnifyKey, b: UnifyKey) -> TractResult<UnifyValue> {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return Ok(self.value[a.index()].clone());
+        }
+        let value = unify_values(&self.value[a.index()], &self.value[b.index()])?;
+        let (root, child) = if self.rank[a.index()] < self.rank[b.index()] { (b, a) } else { (a, b) };
+        self.parent[child.index()] = root;
+        if self.rank[a.index()] == self.rank[b.index()] {
+            self.rank[root.index()] += 1;
+        }
+        self.value[root.index()] = value.clone();
+        Ok(value)
+    }
+
+    /// Pin the class of `key` to a concrete value, reconciling with whatever it
+    /// already carries. Returns the reconciled class value.
+    pub fn unify_var_value(&mut self, key: UnifyKey, value: UnifyValue) -> TractResult<UnifyValue> {
+        let root = self.find(key);
+        let merged = unify_values(&self.value[root.index()], &value)?;
+        self.value[root.index()] = merged.clone();
+        Ok(merged)
+    }
+
+    /// Read the resolved value of `key`'s class, or `Unknown` if still open.
+    pub fn probe_value(&mut self, key: UnifyKey) -> UnifyValue {
+        let root = self.find(key);
+        self.value[root.index()].clone()
+    }
+
+    /// True once `key` has been pinned to a concrete value.
+    pub fn is_known(&mut self, key: UnifyKey) -> bool {
+        !self.probe_value(key).is_unknown()
+    }
+}
+
+/// Backend a rule-based op drives its equalities into.
+///
+/// An op's rules call [`equals`](Self::equals) for a variable-to-variable
+/// equality (a class merge) and [`equals_value`](Self::equals_value) for a
+/// variable-to-concrete one (pinning a class). The solver also owns the
+/// obligation worklist: whenever an equality pins a previously-unknown variable,
+/// the variable's watchers are woken, so parked constraints retry only when a
+/// value they depend on actually changes rather than on every pass.
+#[derive(Default)]
+pub struct UnifySolver {
+    table: UnificationTable,
+    worklist: ObligationWorklist,
+}
+
+impl UnifySolver {
+    pub fn new() -> UnifySolver {
+        UnifySolver::default()
+    }
+
+    /// Allocate a fresh, still-unknown variable.
+    pub fn new_var(&mut self) -> UnifyKey {
+        self.table.new_key()
+    }
+
+    /// Merge two variables' classes, waking any obligation watching the result
+    /// if the merge pinned it.
+    pub fn equals(&mut self, a: UnifyKey, b: UnifyKey) -> TractResult<()> {
+        let before = self.table.is_known(a) && self.table.is_known(b);
+        let value = self.table.union(a, b)?;
+        if !before && !value.is_unknown() {
+            self.worklist.resolved(a);
+            self.worklist.resolved(b);
+        }
+        Ok(())
+    }
+
+    /// Pin a variable's class to a concrete value, waking its watchers.
+    pub fn equals_value(&mut self, key: UnifyKey, value: UnifyValue) -> TractResult<()> {
+        let before = self.table.is_known(key);
+        self.table.unify_var_value(key, value)?;
+        if !before {
+            self.worklist.resolved(key);
+        }
+        Ok(())
+    }
+
+    /// Reconcile the rank of two facts, then pairwise-unify their dimension
+    /// variables.
+    ///
+    /// The rank itself flows through the table's values: both rank keys are
+    /// pinned to `Rank(n)`, so a later equality that pins one to a different
+    /// rank conflicts through the same value machinery as any other quantity.
+    pub fn equals_rank(
+        &mut self,
+        left: (UnifyKey, &[UnifyKey]),
+        right: (UnifyKey, &[UnifyKey]),
+    ) -> TractResult<()> {
+        self.equals_value(left.0, UnifyValue::Rank(left.1.len()))?;
+        self.equals_value(right.0, UnifyValue::Rank(right.1.len()))?;
+        self.equals(left.0, right.0)?;
+        for (a, b) in left.1.iter().zip(right.1.iter()) {
+            self.equals(*a, *b)?;
+        }
+        Ok(())
+    }
+
+    /// Park a constraint that could not resolve yet; it will be handed back once
+    /// one of its watched variables is pinned.
+    pub fn defer(&mut self, obligation: super::Obligation) {
+        self.worklist.register(obligation);
+    }
+
+    /// Pop the next parked constraint whose watched variable just resolved.
+    pub fn next_ready(&mut self) -> Option<super::Obligation> {
+        self.worklist.next_ready()
+    }
+
+    /// Constraints still blocked once the worklist drains.
+    pub fn pending(&self) -> impl Iterator<Item = &super::Obligation> {
+        self.worklist.pending()
+    }
+
+    /// Read a variable back after saturation.
+    pub fn probe(&mut self, key: UnifyKey) -> UnifyValue {
+        self.table.probe_value(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infer::Obligation;
+
+    #[test]
+    fn union_propagates_known_value() {
+        let mut s = UnifySolver::new();
+        let a = s.new_var();
+        let b = s.new_var();
+        s.equals(a, b).unwrap();
+        s.equals_value(a, UnifyValue::Dt(f32::datum_type())).unwrap();
+        assert_eq!(s.probe(b), UnifyValue::Dt(f32::datum_type()));
+    }
+
+    #[test]
+    fn conflicting_known_values_error() {
+        let mut s = UnifySolver::new();
+        let a = s.new_var();
+        let b = s.new_var();
+        s.equals_value(a, UnifyValue::Dt(f32::datum_type())).unwrap();
+        s.equals_value(b, UnifyValue::Dt(i64::datum_type())).unwrap();
+        assert!(s.equals(a, b).is_err());
+    }
+
+    #[test]
+    fn pinning_variable_wakes_watching_obligation() {
+        let mut s = UnifySolver::new();
+        let watched = s.new_var();
+        s.defer(Obligation { watched: tvec![watched], outlets: tvec![], cause: "blocked".into() });
+        assert!(s.next_ready().is_none());
+        s.equals_value(watched, UnifyValue::Dt(f32::datum_type())).unwrap();
+        assert!(s.next_ready().is_some());
+    }
+
+    #[test]
+    fn symbolic_dim_vs_concrete_keeps_concrete() {
+        let sym: TDim = Symbol::new('S').to_dim();
+        let merged = unify_values(&UnifyValue::Dim(sym), &UnifyValue::Dim(3.to_dim())).unwrap();
+        assert_eq!(merged, UnifyValue::Dim(3.to_dim()));
+    }
+
+    #[test]
+    fn two_distinct_symbols_conflict() {
+        let s: TDim = Symbol::new('S').to_dim();
+        let p: TDim = Symbol::new('P').to_dim();
+        assert!(unify_values(&UnifyValue::Dim(s), &UnifyValue::Dim(p)).is_err());
+    }
+
+    #[test]
+    fn two_differing_concrete_dims_conflict() {
+        let r = unify_values(&UnifyValue::Dim(2.to_dim()), &UnifyValue::Dim(3.to_dim()));
+        assert!(r.is_err());
+    }
+
+    #[test]
+    fn rank_conflict_surfaces_through_values() {
+        let mut s = UnifySolver::new();
+        let lr = s.new_var();
+        let rr = s.new_var();
+        let ld = [s.new_var(), s.new_var()];
+        let rd = [s.new_var()];
+        assert!(s.equals_rank((lr, &ld), (rr, &rd)).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::model::{Fact, ModelImpl, OutletId};
+use crate::ops::TypedOp;
 use crate::prelude::*;
 use std::collections::HashMap;
 use std::fmt;
@@ -83,3 +84,280 @@ where
         Ok(node.outputs.iter().enumerate().map(|(ix, _)| OutletId::new(new_id, ix)).collect())
     }
 }
+
+/// The outcome of reconciling a producer fact with a consumer's expected fact.
+pub enum Coercion {
+    /// The facts are already compatible; leave the edge untouched.
+    Compatible,
+    /// Splice this conversion (a `Cast`, typically) onto the edge.
+    Insert(Box<dyn TypedOp>),
+    /// The facts disagree but policy refuses to convert them silently; the
+    /// string explains why so the translator can fail loudly instead of leaving
+    /// a mismatched, unreported edge.
+    Forbidden(String),
+}
+
+/// Decides how to reconcile a producer fact with a consumer's expected fact.
+///
+/// The translator synthesizes a conversion only when the two facts genuinely
+/// disagree, and never silently leaves a mismatch: a disagreement it will not
+/// convert is reported as [`Coercion::Forbidden`].
+pub trait Coercer {
+    fn coerce(&self, from: &TypedFact, to_expected: &TypedFact) -> Coercion;
+}
+
+/// Default coercion policy over datum types.
+///
+/// Widening conversions — a numeric type flowing into a wider one of the same
+/// family and signedness, an unsigned integer into a strictly wider signed one,
+/// or an integer into a float at least as wide as itself — are inserted
+/// implicitly. The integer-to-float case rounds integers beyond the float's
+/// precision (an i32 above 2^24 into f32, for instance). Other conversions
+/// (narrowing, signed into unsigned, or float into integer) are inserted only
+/// when `allow_narrowing` is set, and are otherwise reported as forbidden
+/// rather than dropped.
+#[derive(Clone, Debug, Default)]
+pub struct DefaultCoercer {
+    pub allow_narrowing: bool,
+}
+
+impl DefaultCoercer {
+    fn is_lossless(from: DatumType, to: DatumType) -> bool {
+        if from.is_integer() && to.is_integer() {
+            if from.is_signed() == to.is_signed() {
+                to.size_of() >= from.size_of()
+            } else {
+                // only an unsigned integer fits in a strictly wider signed one.
+                from.is_unsigned() && to.size_of() > from.size_of()
+            }
+        } else if from.is_float() && to.is_float() {
+            to.size_of() >= from.size_of()
+        } else if from.is_integer() && to.is_float() {
+            // integers promote to a float at least as wide as themselves.
+            to.size_of() >= from.size_of()
+        } else {
+            // float -> integer is always lossy.
+            false
+        }
+    }
+}
+
+impl Coercer for DefaultCoercer {
+    fn coerce(&self, from: &TypedFact, to_expected: &TypedFact) -> Coercion {
+        let (from_dt, to_dt) = (from.datum_type, to_expected.datum_type);
+        if from_dt == to_dt {
+            return Coercion::Compatible;
+        }
+        if Self::is_lossless(from_dt, to_dt) || self.allow_narrowing {
+            Coercion::Insert(Box::new(crate::ops::cast::Cast::new(to_dt)))
+        } else {
+            Coercion::Forbidden(format!(
+                "lossy conversion from {:?} to {:?} requires an explicit narrowing policy",
+                from_dt, to_dt
+            ))
+        }
+    }
+}
+
+/// Reports the fact a consuming op requires on a given input.
+///
+/// This is the "expected" side of a coercion: the fact the *consumer* wants on
+/// its `ix`-th input, which is generally not the producer's outlet fact. The
+/// `inputs` slice carries the producer facts actually wired in, so an op whose
+/// requirement depends on its other inputs can compute it. Returning `None`
+/// means the op imposes no datum-type requirement and the edge is left alone.
+pub trait InputExpectation {
+    fn expected_input_fact(
+        &self,
+        op: &dyn TypedOp,
+        ix: usize,
+        inputs: &[&TypedFact],
+    ) -> Option<TypedFact>;
+}
+
+/// Default expectation: no datum-type requirement on any input, so every edge
+/// is left alone. Ops with a hard requirement provide their own
+/// [`InputExpectation`].
+#[derive(Clone, Debug, Default)]
+pub struct NoExpectation;
+
+impl InputExpectation for NoExpectation {
+    fn expected_input_fact(
+        &self,
+        _op: &dyn TypedOp,
+        _ix: usize,
+        _inputs: &[&TypedFact],
+    ) -> Option<TypedFact> {
+        None
+    }
+}
+
+/// A one-to-one translator that inserts conversion nodes on edges whose producer
+/// fact and the consumer's *required* input fact disagree, instead of assuming
+/// every edge already matches.
+///
+/// The `expectation` hook (see [`InputExpectation`]) reports what the consuming
+/// op needs on each input; the `coercer` hook (see [`Coercer`]) decides what to
+/// splice in. A disagreement the coercer refuses to convert aborts translation
+/// rather than passing through a mismatched edge.
+pub struct CoercingTranslator<C: Coercer, E: InputExpectation> {
+    pub coercer: C,
+    pub expectation: E,
+}
+
+impl Default for CoercingTranslator<DefaultCoercer, NoExpectation> {
+    fn default() -> CoercingTranslator<DefaultCoercer, NoExpectation> {
+        CoercingTranslator { coercer: DefaultCoercer::default(), expectation: NoExpectation }
+    }
+}
+
+impl<C: Coercer, E: InputExpectation>
+    Translate<TypedFact, Box<dyn TypedOp>, TypedFact, Box<dyn TypedOp>>
+    for CoercingTranslator<C, E>
+{
+    fn translate_node(
+        &self,
+        _source: &ModelImpl<TypedFact, Box<dyn TypedOp>>,
+        node: &BaseNode<TypedFact, Box<dyn TypedOp>>,
+        target: &mut ModelImpl<TypedFact, Box<dyn TypedOp>>,
+        mapping: &HashMap<OutletId, OutletId>,
+    ) -> TractResult<TVec<OutletId>> {
+        let new_op = node.op.clone();
+        let facts = node.outputs.iter().map(|of| of.fact.clone()).collect::<TVec<_>>();
+        let new_id = target.add_node(node.name.clone(), new_op, facts)?;
+        // facts already wired into `target` for this node's inputs
+        let input_facts = node
+            .inputs
+            .iter()
+            .map(|o| target.outlet_fact(mapping[o]).map(|f| f.clone()))
+            .collect::<TractResult<TVec<_>>>()?;
+        for (ix, o) in node.inputs.iter().enumerate() {
+            let mut wire = mapping[o];
+            let from = target.outlet_fact(wire)?.clone();
+            let borrowed = input_facts.iter().collect::<TVec<_>>();
+            let expected =
+                self.expectation.expected_input_fact(node.op.as_ref(), ix, &borrowed);
+            if let Some(expected) = expected {
+                match self.coercer.coerce(&from, &expected) {
+                    Coercion::Compatible => {}
+                    Coercion::Insert(cast) => {
+                        wire = target.wire_node(
+                            format!("{}-coerce-{}", node.name, ix),
+                            cast,
+                            &[wire],
+                        )?[0];
+                    }
+                    Coercion::Forbidden(why) => bail!(
+                        "{}: cannot coerce input {} ({:?} -> {:?}): {}",
+                        node.name,
+                        ix,
+                        from.datum_type,
+                        expected.datum_type,
+                        why
+                    ),
+                }
+            }
+            target.add_edge(wire, InletId::new(new_id, ix))?
+        }
+        Ok(node.outputs.iter().enumerate().map(|(ix, _)| OutletId::new(new_id, ix)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::dummy::Dummy;
+
+    // A consumer that always requires f32 on its single input, regardless of
+    // what the producer emits.
+    struct WantsF32;
+    impl InputExpectation for WantsF32 {
+        fn expected_input_fact(
+            &self,
+            _op: &dyn TypedOp,
+            _ix: usize,
+            inputs: &[&TypedFact],
+        ) -> Option<TypedFact> {
+            TypedFact::dt_shape(f32::datum_type(), inputs[0].shape.clone()).ok()
+        }
+    }
+
+    #[test]
+    fn inserts_cast_on_f16_to_f32_edge() {
+        // source model: an f16 source feeding a (dummy) consumer.
+        let mut model = TypedModel::default();
+        let src = model
+            .add_source("src", TypedFact::dt_shape(f16::datum_type(), [1].as_ref()).unwrap())
+            .unwrap();
+        let consumer = model.wire_node("consumer", Dummy::new(), &[src]).unwrap()[0];
+        model.set_output_outlets(&[consumer]).unwrap();
+
+        let translator = CoercingTranslator { coercer: DefaultCoercer::default(), expectation: WantsF32 };
+        let translated = translator.translate_model(&model).unwrap();
+
+        // a cast node must now sit between source and consumer.
+        assert!(translated.nodes().iter().any(|n| n.name.contains("coerce")));
+    }
+
+    #[test]
+    fn no_cast_when_dtypes_match() {
+        let mut model = TypedModel::default();
+        let src = model
+            .add_source("src", TypedFact::dt_shape(f32::datum_type(), [1].as_ref()).unwrap())
+            .unwrap();
+        let consumer = model.wire_node("consumer", Dummy::new(), &[src]).unwrap()[0];
+        model.set_output_outlets(&[consumer]).unwrap();
+
+        let translator = CoercingTranslator { coercer: DefaultCoercer::default(), expectation: WantsF32 };
+        let translated = translator.translate_model(&model).unwrap();
+        assert!(!translated.nodes().iter().any(|n| n.name.contains("coerce")));
+    }
+
+    #[test]
+    fn cross_family_int_to_float_is_cast() {
+        // an i32 producer into an f32-required input counts as a widening and
+        // is inserted without a narrowing policy.
+        let mut model = TypedModel::default();
+        let src = model
+            .add_source("src", TypedFact::dt_shape(i32::datum_type(), [1].as_ref()).unwrap())
+            .unwrap();
+        let consumer = model.wire_node("consumer", Dummy::new(), &[src]).unwrap()[0];
+        model.set_output_outlets(&[consumer]).unwrap();
+
+        let translator = CoercingTranslator { coercer: DefaultCoercer::default(), expectation: WantsF32 };
+        let translated = translator.translate_model(&model).unwrap();
+        assert!(translated.nodes().iter().any(|n| n.name.contains("coerce")));
+    }
+
+    // A consumer requiring i32, to exercise a lossy float -> int disagreement.
+    struct WantsI32;
+    impl InputExpectation for WantsI32 {
+        fn expected_input_fact(
+            &self,
+            _op: &dyn TypedOp,
+            _ix: usize,
+            inputs: &[&TypedFact],
+        ) -> Option<TypedFact> {
+            TypedFact::dt_shape(i32::datum_type(), inputs[0].shape.clone()).ok()
+        }
+    }
+
+    #[test]
+    fn lossy_mismatch_is_reported_not_silently_dropped() {
+        let mut model = TypedModel::default();
+        let src = model
+            .add_source("src", TypedFact::dt_shape(f32::datum_type(), [1].as_ref()).unwrap())
+            .unwrap();
+        let consumer = model.wire_node("consumer", Dummy::new(), &[src]).unwrap()[0];
+        model.set_output_outlets(&[consumer]).unwrap();
+
+        let translator = CoercingTranslator { coercer: DefaultCoercer::default(), expectation: WantsI32 };
+        // without a narrowing policy the f32 -> i32 edge is refused, not ignored.
+        assert!(translator.translate_model(&model).is_err());
+
+        let permissive =
+            CoercingTranslator { coercer: DefaultCoercer { allow_narrowing: true }, expectation: WantsI32 };
+        let translated = permissive.translate_model(&model).unwrap();
+        assert!(translated.nodes().iter().any(|n| n.name.contains("coerce")));
+    }
+}